@@ -1,10 +1,45 @@
-use crate::common::model::DatabaseType;
+use crate::common::model::{CompressionType, DatabaseType};
 use anyhow::bail;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(name = "backup", arg_required_else_help = true, about, version, author)]
-pub struct Args {
+pub struct Cli {
+  /// Dotenv file path
+  #[arg(long, global = true)]
+  pub env_file: Option<String>,
+
+  /// Enable debug log
+  #[arg(long, global = true)]
+  pub debug: bool,
+
+  /// Outbound proxy (SOCKS5 or HTTP) used for ntfy notifications and rclone transfers.
+  /// Falls back to the ALL_PROXY environment variable when unset.
+  #[arg(long, env = "HTTP_PROXY", global = true, verbatim_doc_comment)]
+  pub proxy: Option<String>,
+
+  /// Maximum number of attempts for ntfy notifications and rclone transfers
+  #[arg(long, env = "BACKUP_MAX_RETRIES", default_value = "3", global = true)]
+  pub max_retries: u32,
+
+  /// Base backoff in milliseconds between retries, doubled after each failed attempt
+  #[arg(long, env = "BACKUP_RETRY_BACKOFF_MS", default_value = "500", global = true)]
+  pub retry_backoff_ms: u64,
+
+  #[command(subcommand)]
+  pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+  /// Create a new backup and upload it to the configured remotes
+  Backup(BackupArgs),
+  /// Download, verify, decrypt and unpack a backup from a remote
+  Restore(RestoreArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct BackupArgs {
   /// Backup data directory
   /// Backup data directory from docker when it starts with docker://
   /// e.g. docker://container_name:/path/to/data
@@ -15,17 +50,39 @@ pub struct Args {
   #[clap(flatten)]
   pub database_args: DatabaseArgs,
 
+  /// SSH private key path used to authenticate to ssh:// data sources, falls back to the ssh-agent
+  #[arg(long, env = "BACKUP_SSH_KEY")]
+  pub ssh_key: Option<String>,
+
+  /// SSH port used to connect to ssh:// data sources
+  #[arg(long, env = "BACKUP_SSH_PORT", default_value = "22")]
+  pub ssh_port: u16,
+
+  /// Known hosts file used to verify the host key of ssh:// data sources (OpenSSH format).
+  /// Required to connect; the connection is refused if the host key is missing or mismatched.
+  #[arg(long, env = "BACKUP_SSH_KNOWN_HOSTS", verbatim_doc_comment)]
+  pub ssh_known_hosts: Option<String>,
+
   /// Exclude files matching pattern
   #[arg(long)]
   pub exclude: Option<Vec<String>>,
 
+  /// Compression backend used for the backup archive
+  #[arg(long, env = "BACKUP_COMPRESSION", value_enum, default_value = "gzip")]
+  pub compression: CompressionType,
+
+  /// Compression level passed to the selected backend
+  #[arg(long, env = "BACKUP_COMPRESSION_LEVEL")]
+  pub compression_level: Option<i32>,
+
   /// Backup rotate
   #[arg(long, env = "BACKUP_ROTATE", default_value = "30")]
   pub rotate: usize,
 
-  /// Dotenv file path
-  #[arg(long)]
-  pub env_file: Option<String>,
+  /// Age recipients (age1... public keys) used to encrypt the backup archive.
+  /// Can be passed multiple times. When omitted the archive is uploaded unencrypted.
+  #[arg(long, env = "BACKUP_AGE_RECIPIENT", value_delimiter = ',', verbatim_doc_comment)]
+  pub age_recipient: Option<Vec<String>>,
 
   /// Rclone arguments
   #[clap(flatten)]
@@ -34,10 +91,25 @@ pub struct Args {
   /// Ntfy arguments
   #[clap(flatten)]
   pub ntfy_args: NtfyArgs,
+}
 
-  /// Enable debug log
+#[derive(Parser, Debug)]
+pub struct RestoreArgs {
+  /// Rclone arguments
+  #[clap(flatten)]
+  pub rclone_args: RcloneArgs,
+
+  /// Restore a specific backup by timestamp (format: %Y%m%d_%H%M%S) instead of the newest one
   #[arg(long)]
-  pub debug: bool,
+  pub at: Option<String>,
+
+  /// Directory to unpack the restored backup into
+  #[arg(long, env = "BACKUP_RESTORE_TARGET")]
+  pub restore_target: String,
+
+  /// Age identity file used to decrypt an encrypted backup archive
+  #[arg(long, env = "BACKUP_AGE_IDENTITY")]
+  pub age_identity: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -49,6 +121,26 @@ pub struct DatabaseArgs {
   /// Database Container name
   #[arg(long, env = "DB_CONTAINER_NAME")]
   pub db_container_name: Option<String>,
+
+  /// Database host, dumps directly over TCP instead of through a docker container
+  #[arg(long, env = "DB_HOST")]
+  pub db_host: Option<String>,
+
+  /// Database port
+  #[arg(long, env = "DB_PORT")]
+  pub db_port: Option<u16>,
+
+  /// Database user
+  #[arg(long, env = "DB_USER")]
+  pub db_user: Option<String>,
+
+  /// Database password
+  #[arg(long, env = "DB_PASSWORD")]
+  pub db_password: Option<String>,
+
+  /// Database name
+  #[arg(long, env = "DB_NAME")]
+  pub db_name: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -89,7 +181,7 @@ pub struct NtfyArgs {
   pub ntfy_topic: Option<String>,
 }
 
-impl Args {
+impl BackupArgs {
   pub fn check_valid(&self) -> anyhow::Result<()> {
     let data_path = self.data_path.as_deref().unwrap_or(&[]);
     if data_path.is_empty() {
@@ -104,6 +196,11 @@ impl Args {
       bail!("The exclude pattern can not be empty");
     }
 
+    let age_recipient = self.age_recipient.as_deref().unwrap_or(&[]);
+    if age_recipient.iter().any(|s| s.is_empty()) {
+      bail!("The age recipient can not be empty");
+    }
+
     self.database_args.check_valid()?;
     self.rclone_args.check_valid()?;
     self.ntfy_args.check_valid()?;
@@ -112,24 +209,62 @@ impl Args {
   }
 }
 
+impl RestoreArgs {
+  pub fn check_valid(&self) -> anyhow::Result<()> {
+    if self.restore_target.is_empty() {
+      bail!("The restore target directory is required");
+    }
+    self.rclone_args.check_valid()?;
+
+    let remote_name = self.rclone_args.rclone_remote_name.as_deref().unwrap_or(&[]);
+    if remote_name.len() > 1 {
+      bail!(
+        "Restore requires exactly one rclone remote, but {} were configured: {}. Pass a single --rclone-remote-name",
+        remote_name.len(),
+        remote_name.join(", ")
+      );
+    }
+    Ok(())
+  }
+}
+
 impl DatabaseArgs {
   pub fn check_valid(&self) -> anyhow::Result<()> {
     let container_name = self.db_container_name.as_deref().unwrap_or("");
-    if self.db_type.is_none() && container_name.is_empty() {
+    let host = self.db_host.as_deref().unwrap_or("");
+
+    if self.db_type.is_none() && container_name.is_empty() && host.is_empty() {
       return Ok(());
     }
-    if self.db_type.is_none() && !container_name.is_empty() {
+    if self.db_type.is_none() {
       bail!("The database type is required");
     }
-    if self.db_type.is_some() && container_name.is_empty() {
-      bail!("The database container name is required");
+    if !container_name.is_empty() && !host.is_empty() {
+      bail!("The database container name and the database host can not be set at the same time");
+    }
+    if container_name.is_empty() && host.is_empty() {
+      bail!("Either the database container name or the database host is required");
+    }
+
+    if !host.is_empty() {
+      let user = self.db_user.as_deref().unwrap_or("");
+      let password = self.db_password.as_deref().unwrap_or("");
+      let db_name = self.db_name.as_deref().unwrap_or("");
+      if user.is_empty() || password.is_empty() || db_name.is_empty() {
+        bail!("The database user, password and name are required when using a database host");
+      }
     }
     Ok(())
   }
 
   pub fn has_args(&self) -> bool {
     let container_name = self.db_container_name.as_deref().unwrap_or("");
-    self.db_type.is_some() && !container_name.is_empty()
+    let host = self.db_host.as_deref().unwrap_or("");
+    self.db_type.is_some() && (!container_name.is_empty() || !host.is_empty())
+  }
+
+  pub fn is_host_mode(&self) -> bool {
+    self.db_host.as_deref().map(|s| !s.is_empty()).unwrap_or(false)
   }
 }
 
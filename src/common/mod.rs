@@ -1,5 +1,7 @@
-use crate::common::model::{DatabaseType, RcloneLs};
+use crate::common::model::{CompressionType, DatabaseType, RcloneLs};
 use anyhow::bail;
+use std::io::Write;
+use std::str::FromStr;
 use tracing::{debug, info, warn};
 
 pub mod model;
@@ -37,6 +39,86 @@ pub async fn copy_files_by_docker(src: &String, dest: &String) -> anyhow::Result
   Ok(())
 }
 
+pub async fn copy_files_by_ssh(
+  src: &String,
+  dest: &String,
+  ssh_key: &Option<String>,
+  ssh_port: &u16,
+  ssh_known_hosts: &Option<String>,
+) -> anyhow::Result<()> {
+  let (user, rest) = src
+    .split_once('@')
+    .ok_or_else(|| anyhow::anyhow!("invalid ssh source [{src}], expected user@host:/path"))?;
+  let (host, remote_path) = rest
+    .split_once(':')
+    .ok_or_else(|| anyhow::anyhow!("invalid ssh source [{src}], expected user@host:/path"))?;
+  let user = user.to_string();
+  let host = host.to_string();
+  let remote_path = remote_path.to_string();
+  let dest = dest.clone();
+  let ssh_key = ssh_key.clone();
+  let ssh_port = *ssh_port;
+  let ssh_known_hosts = ssh_known_hosts.clone();
+
+  let user_clone = user.clone();
+  let host_clone = host.clone();
+  let dest_clone = dest.clone();
+  tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+    let tcp = std::net::TcpStream::connect((host.as_str(), ssh_port))?;
+    let mut session = ssh2::Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+    verify_host_key(&session, &host, ssh_port, &ssh_known_hosts)?;
+    match &ssh_key {
+      Some(key_path) => session.userauth_pubkey_file(&user, None, std::path::Path::new(key_path), None)?,
+      None => session.userauth_agent(&user)?,
+    }
+    if !session.authenticated() {
+      bail!("failed to authenticate to ssh host [{host}] as [{user}]");
+    }
+    let mut channel = session.channel_session()?;
+    channel.exec(&format!("tar c -C {} .", shell_quote(&remote_path)))?;
+    let mut archive = tar::Archive::new(&mut channel);
+    archive.unpack(&dest)?;
+    channel.wait_close()?;
+    let exit_status = channel.exit_status()?;
+    if exit_status != 0 {
+      bail!("remote tar command on [{host}] exited with status [{exit_status}]");
+    }
+    Ok(())
+  })
+  .await??;
+  debug!("copy files from [{user_clone}@{host_clone}] to [{dest_clone}] by ssh");
+  Ok(())
+}
+
+/// Verifies the remote's host key against a known_hosts file, refusing to proceed on a
+/// mismatch (possible man-in-the-middle) or when no known_hosts file was supplied.
+fn verify_host_key(session: &ssh2::Session, host: &str, port: u16, ssh_known_hosts: &Option<String>) -> anyhow::Result<()> {
+  let known_hosts_path = ssh_known_hosts
+    .as_ref()
+    .ok_or_else(|| anyhow::anyhow!("--ssh-known-hosts is required to verify the ssh host key for [{host}]"))?;
+  let (key, _) = session
+    .host_key()
+    .ok_or_else(|| anyhow::anyhow!("failed to get ssh host key for [{host}]"))?;
+
+  let mut known_hosts = session.known_hosts()?;
+  known_hosts.read_file(std::path::Path::new(known_hosts_path), ssh2::KnownHostFileKind::OpenSSH)?;
+  match known_hosts.check_port(host, port, key) {
+    ssh2::CheckResult::Match => Ok(()),
+    ssh2::CheckResult::NotFound => bail!("ssh host key for [{host}] not found in known_hosts file [{known_hosts_path}]"),
+    ssh2::CheckResult::Mismatch => bail!(
+      "ssh host key for [{host}] does not match the entry in known_hosts file [{known_hosts_path}], refusing to connect (possible man-in-the-middle attack)"
+    ),
+    ssh2::CheckResult::Failure => bail!("failed to check ssh host key for [{host}] against known_hosts file [{known_hosts_path}]"),
+  }
+}
+
+/// Single-quotes a value for safe interpolation into a remote shell command, escaping embedded single quotes.
+fn shell_quote(value: &str) -> String {
+  format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 pub async fn dump_db_by_docker(
   db_dump_path: &String,
   container_name: &String,
@@ -69,31 +151,86 @@ pub async fn dump_db_by_docker(
   Ok(())
 }
 
+pub async fn dump_db_by_host(
+  db_dump_path: &String,
+  host: &String,
+  port: &Option<u16>,
+  user: &String,
+  password: &String,
+  db_name: &String,
+  db_type: &DatabaseType,
+) -> anyhow::Result<()> {
+  let output = match db_type {
+    DatabaseType::Mysql => {
+      let port = port.unwrap_or(3306);
+      tokio::process::Command::new("mysqldump")
+        .env("MYSQL_PWD", password)
+        .arg(format!("-h{host}"))
+        .arg(format!("-P{port}"))
+        .arg(format!("-u{user}"))
+        .arg("--databases")
+        .arg(db_name)
+        .arg("--no-tablespaces")
+        .output()
+        .await?
+    }
+    DatabaseType::Postgres => {
+      let port = port.unwrap_or(5432);
+      tokio::process::Command::new("pg_dump")
+        .env("PGPASSWORD", password)
+        .arg(format!("-h{host}"))
+        .arg(format!("-p{port}"))
+        .arg(format!("-U{user}"))
+        .arg(db_name)
+        .arg("--clean")
+        .output()
+        .await?
+    }
+  };
+  if output.status.success() {
+    tokio::fs::write(db_dump_path, &output.stdout)
+      .await
+      .expect("failed to write database backup data to file");
+    debug!("dump database data to [{db_dump_path}] from host [{host}]");
+  } else {
+    bail!("failed to dump database: {}", String::from_utf8(output.stderr)?);
+  }
+  Ok(())
+}
+
 pub async fn compress_and_sign(
   src: &String,
   exclude: &Option<Vec<String>>,
   compress_file_name: &String,
   compress_sha256_file_name: &String,
+  compression: &CompressionType,
+  compression_level: &Option<i32>,
+  age_recipient: &Option<Vec<String>>,
 ) -> anyhow::Result<()> {
-  // compress
-  let mut command = tokio::process::Command::new("tar");
-  command.arg("-zcvf").arg(compress_file_name);
-  if let Some(pattern_vec) = exclude {
-    for pattern in pattern_vec {
-      command.arg("--exclude").arg(pattern);
-    }
-  }
-  command.arg(src);
-  let output = command.output().await?;
-  if output.status.success() {
-    debug!(
-      "compress file, current_dir: {}\n{}",
-      std::env::current_dir()?.display(),
-      String::from_utf8(output.stdout)?
-    );
-  } else {
-    bail!("failed to compress: {}", String::from_utf8(output.stderr)?);
+  // compress, writing to a plain archive first; when requested it is encrypted into the .age file below and removed
+  let tar_file_name = compress_file_name.strip_suffix(".age").unwrap_or(compress_file_name).to_string();
+  let src = src.clone();
+  let exclude = exclude.clone().unwrap_or_default();
+  let tar_file_name_clone = tar_file_name.clone();
+  let compression = *compression;
+  let compression_level = *compression_level;
+  tokio::task::spawn_blocking(move || compress_dir(&src, &exclude, &tar_file_name_clone, compression, compression_level))
+    .await??;
+  debug!(
+    "compress file [{}] with {:?} compression, current_dir: {}",
+    tar_file_name,
+    compression,
+    std::env::current_dir()?.display(),
+  );
+
+  // encrypt
+  let age_recipient = age_recipient.as_deref().unwrap_or(&[]);
+  if !age_recipient.is_empty() {
+    encrypt_file(&tar_file_name, compress_file_name, age_recipient).await?;
+    tokio::fs::remove_file(&tar_file_name).await?;
+    debug!("encrypt file: {} -> {}", tar_file_name, compress_file_name);
   }
+
   // sign
   let output = tokio::process::Command::new("shasum")
     .arg("--algorithm")
@@ -113,20 +250,294 @@ pub async fn compress_and_sign(
   Ok(())
 }
 
+fn compress_dir(
+  src: &str,
+  exclude: &[String],
+  out_path: &str,
+  compression: CompressionType,
+  compression_level: Option<i32>,
+) -> anyhow::Result<()> {
+  let file = std::fs::File::create(out_path)?;
+  match compression {
+    CompressionType::Gzip => {
+      let level = compression_level.unwrap_or(6).clamp(0, 9) as u32;
+      let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::new(level));
+      write_tar(src, exclude, &mut encoder)?;
+      encoder.finish()?;
+    }
+    CompressionType::Zstd => {
+      let level = compression_level.unwrap_or(19);
+      let mut encoder = zstd::Encoder::new(file, level)?;
+      write_tar(src, exclude, &mut encoder)?;
+      encoder.finish()?;
+    }
+    CompressionType::Brotli => {
+      let level = compression_level.unwrap_or(11).clamp(0, 11) as u32;
+      let mut encoder = brotli::CompressorWriter::new(file, 4096, level, 22);
+      write_tar(src, exclude, &mut encoder)?;
+      encoder.flush()?;
+    }
+    CompressionType::None => {
+      write_tar(src, exclude, file)?;
+    }
+  }
+  Ok(())
+}
+
+fn write_tar<W: std::io::Write>(src: &str, exclude: &[String], writer: W) -> anyhow::Result<()> {
+  let patterns = exclude
+    .iter()
+    .map(|pattern| glob::Pattern::new(pattern))
+    .collect::<Result<Vec<_>, _>>()?;
+  let mut builder = tar::Builder::new(writer);
+  let src_path = std::path::Path::new(src);
+  for entry in walkdir::WalkDir::new(src_path).into_iter().filter_map(Result::ok) {
+    let path = entry.path();
+    let relative_path = path.strip_prefix(src_path)?;
+    if relative_path.as_os_str().is_empty() {
+      continue;
+    }
+    let relative_path_str = relative_path.to_string_lossy();
+    if patterns.iter().any(|pattern| pattern.matches(&relative_path_str)) {
+      continue;
+    }
+    if entry.file_type().is_file() {
+      builder.append_path_with_name(path, relative_path)?;
+    } else if entry.file_type().is_dir() {
+      builder.append_dir(relative_path, path)?;
+    } else if entry.file_type().is_symlink() {
+      let target = std::fs::read_link(path)?;
+      let mut header = tar::Header::new_gnu();
+      header.set_metadata(&std::fs::symlink_metadata(path)?);
+      builder.append_link(&mut header, relative_path, &target)?;
+    }
+  }
+  builder.finish()?;
+  Ok(())
+}
+
+async fn encrypt_file(src: &String, dest: &String, recipients: &[String]) -> anyhow::Result<()> {
+  let src = src.clone();
+  let dest = dest.clone();
+  let recipients = recipients.to_vec();
+  tokio::task::spawn_blocking(move || encrypt_file_sync(&src, &dest, &recipients)).await??;
+  Ok(())
+}
+
+fn encrypt_file_sync(src: &str, dest: &str, recipients: &[String]) -> anyhow::Result<()> {
+  let recipients = recipients
+    .iter()
+    .map(|s| {
+      age::x25519::Recipient::from_str(s)
+        .map(|r| Box::new(r) as Box<dyn age::Recipient + Send>)
+        .map_err(|err| anyhow::anyhow!("invalid age recipient '{s}': {err}"))
+    })
+    .collect::<anyhow::Result<Vec<_>>>()?;
+  let encryptor = age::Encryptor::with_recipients(recipients).ok_or_else(|| anyhow::anyhow!("no age recipients"))?;
+
+  let mut source = std::fs::File::open(src)?;
+  let dest_file = std::fs::File::create(dest)?;
+  let mut writer = encryptor.wrap_output(dest_file)?;
+  std::io::copy(&mut source, &mut writer)?;
+  writer.finish()?;
+  Ok(())
+}
+
+pub async fn restore_by_rclone(
+  remote_name: &String,
+  remote_path: &String,
+  bin_path: &String,
+  at: &Option<String>,
+  restore_target: &String,
+  age_identity: &Option<String>,
+  proxy: &Option<String>,
+  max_retries: &u32,
+  retry_backoff_ms: &u64,
+) -> anyhow::Result<()> {
+  let remote = format!("{remote_name}:{remote_path}");
+  let mut lsjson_command = tokio::process::Command::new(bin_path);
+  if let Some(proxy) = proxy {
+    lsjson_command.arg("--http-proxy").arg(proxy);
+  }
+  let output = lsjson_command.arg("lsjson").arg(&remote).output().await?;
+  if !output.status.success() {
+    bail!(
+      "failed to ls json by rclone, error: {}",
+      String::from_utf8(output.stderr)?
+    );
+  }
+  let mut rclone_ls_vec: Vec<RcloneLs> = serde_json::from_str(&String::from_utf8(output.stdout)?)?;
+  rclone_ls_vec.retain(|rclone_ls| rclone_ls.name.starts_with("backup_") && !rclone_ls.name.ends_with(".sha256"));
+  rclone_ls_vec.sort_by(|o1, o2| o2.mod_time.cmp(&o1.mod_time));
+
+  let archive = match at {
+    Some(timestamp) => rclone_ls_vec
+      .into_iter()
+      .find(|rclone_ls| rclone_ls.name.contains(timestamp.as_str()))
+      .ok_or_else(|| anyhow::anyhow!("no backup found at [{timestamp}] on remote [{remote}]"))?,
+    None => rclone_ls_vec
+      .into_iter()
+      .next()
+      .ok_or_else(|| anyhow::anyhow!("no backup found on remote [{remote}]"))?,
+  };
+  info!("restoring [{}] from remote [{}]", archive.name, remote);
+
+  // copy the archive and its signature
+  let sha256_name = format!("{}.sha256", archive.name);
+  for name in [&archive.name, &sha256_name] {
+    let remote_file = format!("{remote_name}:{remote_path}/{name}");
+    let mut attempt = 0;
+    let output = loop {
+      attempt += 1;
+      let mut command = tokio::process::Command::new(bin_path);
+      if let Some(proxy) = proxy {
+        command.arg("--http-proxy").arg(proxy);
+      }
+      let output = command.arg("copy").arg(&remote_file).arg(".").output().await?;
+      if output.status.success() || attempt >= *max_retries {
+        break output;
+      }
+      warn!(
+        "failed to copy file [{}] by rclone (attempt {attempt}/{max_retries}), error: {}, retrying...",
+        remote_file,
+        String::from_utf8_lossy(&output.stderr)
+      );
+      tokio::time::sleep(backoff_duration(*retry_backoff_ms, attempt)).await;
+    };
+    if !output.status.success() {
+      bail!(
+        "failed to copy file [{}] by rclone, error: {}",
+        remote_file,
+        String::from_utf8(output.stderr)?
+      );
+    }
+  }
+
+  // verify integrity against the stored sha256 signature
+  let output = tokio::process::Command::new("shasum")
+    .arg("--algorithm")
+    .arg("256")
+    .arg("--check")
+    .arg(&sha256_name)
+    .output()
+    .await?;
+  if !output.status.success() {
+    bail!(
+      "sha256 mismatch for [{}], error: {}",
+      archive.name,
+      String::from_utf8(output.stderr)?
+    );
+  }
+  info!("sha256 verified for [{}]", archive.name);
+
+  // decrypt, then decompress and unpack into the restore target
+  let mut archive_path = archive.name.clone();
+  if let Some(decrypted_path) = archive_path.strip_suffix(".age").map(str::to_string) {
+    let identity_path = age_identity
+      .as_ref()
+      .ok_or_else(|| anyhow::anyhow!("an age identity is required to decrypt [{archive_path}]"))?;
+    decrypt_file(&archive_path, &decrypted_path, identity_path).await?;
+    debug!("decrypt file: {} -> {}", archive_path, decrypted_path);
+    archive_path = decrypted_path;
+  }
+
+  tokio::fs::create_dir_all(restore_target).await?;
+  let restore_target = restore_target.clone();
+  let archive_path_clone = archive_path.clone();
+  tokio::task::spawn_blocking(move || unpack_archive(&archive_path_clone, &restore_target)).await??;
+  info!("restored [{}] to [{}]", archive.name, restore_target);
+  Ok(())
+}
+
+async fn decrypt_file(src: &String, dest: &String, identity_path: &String) -> anyhow::Result<()> {
+  let identity_content = tokio::fs::read_to_string(identity_path).await?;
+  let src = src.clone();
+  let dest = dest.clone();
+  tokio::task::spawn_blocking(move || decrypt_file_sync(&src, &dest, &identity_content)).await??;
+  Ok(())
+}
+
+fn decrypt_file_sync(src: &str, dest: &str, identity_content: &str) -> anyhow::Result<()> {
+  let identity = age::x25519::Identity::from_str(identity_content.trim())
+    .map_err(|err| anyhow::anyhow!("invalid age identity: {err}"))?;
+
+  let source = std::fs::File::open(src)?;
+  let decryptor = age::Decryptor::new(source)?;
+  let identities: [&dyn age::Identity; 1] = [&identity];
+  let mut reader = decryptor.decrypt(identities.into_iter())?;
+  let mut dest_file = std::fs::File::create(dest)?;
+  std::io::copy(&mut reader, &mut dest_file)?;
+  Ok(())
+}
+
+fn unpack_archive(path: &str, dest: &str) -> anyhow::Result<()> {
+  let file = std::fs::File::open(path)?;
+  if path.ends_with(".tar.gz") {
+    tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(dest)?;
+  } else if path.ends_with(".tar.zst") {
+    tar::Archive::new(zstd::Decoder::new(file)?).unpack(dest)?;
+  } else if path.ends_with(".tar.br") {
+    tar::Archive::new(brotli::Decompressor::new(file, 4096)).unpack(dest)?;
+  } else if path.ends_with(".tar") {
+    tar::Archive::new(file).unpack(dest)?;
+  } else {
+    bail!("unrecognized archive extension for [{path}]");
+  }
+  Ok(())
+}
+
+fn rclone_command(bin_path: &str, proxy: &Option<String>) -> std::process::Command {
+  let mut command = std::process::Command::new(bin_path);
+  if let Some(proxy) = proxy {
+    command.arg("--http-proxy").arg(proxy);
+  }
+  command
+}
+
+/// Computes an exponential backoff duration for the given attempt, clamping the exponent so
+/// the computation cannot overflow regardless of how large `--max-retries` is configured.
+pub(crate) fn backoff_duration(base_ms: u64, attempt: u32) -> std::time::Duration {
+  let exponent = attempt.saturating_sub(1).min(32);
+  std::time::Duration::from_millis(base_ms.saturating_mul(2u64.saturating_pow(exponent)))
+}
+
+fn run_with_retry(
+  max_retries: &u32,
+  retry_backoff_ms: &u64,
+  mut build: impl FnMut() -> std::process::Command,
+) -> anyhow::Result<std::process::Output> {
+  let mut attempt = 0;
+  loop {
+    attempt += 1;
+    let output = build().output()?;
+    if output.status.success() || attempt >= *max_retries {
+      return Ok(output);
+    }
+    warn!(
+      "rclone command failed (attempt {attempt}/{max_retries}), error: {}, retrying...",
+      String::from_utf8_lossy(&output.stderr)
+    );
+    std::thread::sleep(backoff_duration(*retry_backoff_ms, attempt));
+  }
+}
+
 pub fn upload_by_rclone(
   remote_name: &String,
   remote_path: &String,
   local_path: &Vec<String>,
   bin_path: &String,
   rotate: &usize,
+  proxy: &Option<String>,
+  max_retries: &u32,
+  retry_backoff_ms: &u64,
 ) -> anyhow::Result<()> {
   let remote = format!("{remote_name}:{remote_path}");
   for local in local_path {
-    let output = std::process::Command::new(bin_path)
-      .arg("copy")
-      .arg(local)
-      .arg(&remote)
-      .output()?;
+    let output = run_with_retry(max_retries, retry_backoff_ms, || {
+      let mut command = rclone_command(bin_path, proxy);
+      command.arg("copy").arg(local).arg(&remote);
+      command
+    })?;
     if output.status.success() {
       info!("copy file from [{}] to [{}] by rclone", local, remote);
     } else {
@@ -138,10 +549,7 @@ pub fn upload_by_rclone(
       );
     }
   }
-  let output = std::process::Command::new(bin_path)
-    .arg("lsjson")
-    .arg(&remote)
-    .output()?;
+  let output = rclone_command(bin_path, proxy).arg("lsjson").arg(&remote).output()?;
   if !output.status.success() {
     bail!(
       "failed to ls json by rclone, error: {}",
@@ -158,10 +566,11 @@ pub fn upload_by_rclone(
     for _ in 0..cut_count {
       if let Some(rclone_ls) = rclone_ls_vec.pop() {
         let remote = format!("{}:{}/{}", remote_name, remote_path, rclone_ls.name);
-        let output = std::process::Command::new(bin_path)
-          .arg("deletefile")
-          .arg(&remote)
-          .output()?;
+        let output = run_with_retry(max_retries, retry_backoff_ms, || {
+          let mut command = rclone_command(bin_path, proxy);
+          command.arg("deletefile").arg(&remote);
+          command
+        })?;
         if output.status.success() {
           info!("delete [{}] by rclone", remote);
         } else {
@@ -7,6 +7,27 @@ pub enum DatabaseType {
   Postgres,
 }
 
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CompressionType {
+  #[default]
+  Gzip,
+  Zstd,
+  Brotli,
+  None,
+}
+
+impl CompressionType {
+  /// File extension appended after `.tar`, e.g. `gz` for `.tar.gz`. Empty for `none`.
+  pub fn extension(&self) -> &'static str {
+    match self {
+      CompressionType::Gzip => "gz",
+      CompressionType::Zstd => "zst",
+      CompressionType::Brotli => "br",
+      CompressionType::None => "",
+    }
+  }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RcloneLs {
   #[serde(rename = "Path")]
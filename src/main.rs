@@ -18,8 +18,8 @@ async fn main() -> anyhow::Result<()> {
   let is_load_dotenv;
 
   // parse cli and load .env file
-  let args = cli::Args::parse();
-  if let Some(env_file) = &args.env_file {
+  let cli = cli::Cli::parse();
+  if let Some(env_file) = &cli.env_file {
     if dotenvy::from_path(env_file).is_err() {
       bail!("Can not load .env file from '{env_file}'");
     }
@@ -29,15 +29,14 @@ async fn main() -> anyhow::Result<()> {
   }
 
   // reparse cli
-  let args = cli::Args::parse();
+  let cli = cli::Cli::parse();
   if env::var("RUST_LOG").is_err() {
-    if args.debug {
+    if cli.debug {
       env::set_var("RUST_LOG", "backup=debug,reqwest=debug");
     } else {
       env::set_var("RUST_LOG", "backup=info,reqwest=warn");
     }
   }
-  args.check_valid()?;
 
   // tracing
   tracing_subscriber::fmt()
@@ -50,11 +49,40 @@ async fn main() -> anyhow::Result<()> {
     info!("Can not detect .env file");
   }
 
+  let proxy = cli.proxy.or_else(|| env::var("ALL_PROXY").ok());
+
+  match cli.command {
+    cli::Command::Backup(args) => run_backup(args, proxy, cli.max_retries, cli.retry_backoff_ms).await?,
+    cli::Command::Restore(args) => run_restore(args, proxy, cli.max_retries, cli.retry_backoff_ms).await?,
+  }
+
+  let duration = format!("{:.2}", (std::time::Instant::now() - start_time).as_secs_f64());
+  info!("Completed in {} seconds", duration);
+  Ok(())
+}
+
+async fn run_backup(args: cli::BackupArgs, proxy: Option<String>, max_retries: u32, retry_backoff_ms: u64) -> anyhow::Result<()> {
+  args.check_valid()?;
+
   let temp_dir = TempDir::new()?;
   let temp_data_dir_name = String::from("backup_data");
   let temp_data_dir = format!("{}/{temp_data_dir_name}", temp_dir.path().to_string_lossy());
   let now = Local::now();
-  let data_compress_file_name = format!("backup_{}.tar.gz", now.format("%Y%m%d_%H%M%S"));
+  let age_recipient = args.age_recipient.clone();
+  let has_age_recipient = age_recipient.as_deref().map(|v| !v.is_empty()).unwrap_or(false);
+  let compression_extension = args.compression.extension();
+  let data_compress_file_name = {
+    let base = if compression_extension.is_empty() {
+      format!("backup_{}.tar", now.format("%Y%m%d_%H%M%S"))
+    } else {
+      format!("backup_{}.tar.{compression_extension}", now.format("%Y%m%d_%H%M%S"))
+    };
+    if has_age_recipient {
+      format!("{base}.age")
+    } else {
+      base
+    }
+  };
   let data_compress_sha256_file_name = format!("{}.sha256", &data_compress_file_name);
 
   fs::create_dir_all(&temp_data_dir)?;
@@ -63,14 +91,30 @@ async fn main() -> anyhow::Result<()> {
 
   // copy source data to temp data directory
   let data_path = args.data_path.unwrap();
-  let (docker_data_path, non_docker_data_path): (Vec<String>, Vec<String>) =
-    data_path.into_iter().partition(|s| s.starts_with("docker://"));
+  let mut docker_data_path = Vec::new();
+  let mut ssh_data_path = Vec::new();
+  let mut non_docker_data_path = Vec::new();
+  for path in data_path.into_iter() {
+    if path.starts_with("docker://") {
+      docker_data_path.push(path);
+    } else if path.starts_with("ssh://") {
+      ssh_data_path.push(path);
+    } else {
+      non_docker_data_path.push(path);
+    }
+  }
   if !docker_data_path.is_empty() {
     for path in docker_data_path.iter() {
       let src = path.strip_prefix("docker://").unwrap().to_string();
       common::copy_files_by_docker(&src, &temp_data_dir).await?;
     }
   }
+  if !ssh_data_path.is_empty() {
+    for path in ssh_data_path.iter() {
+      let src = path.strip_prefix("ssh://").unwrap().to_string();
+      common::copy_files_by_ssh(&src, &temp_data_dir, &args.ssh_key, &args.ssh_port, &args.ssh_known_hosts).await?;
+    }
+  }
   if !non_docker_data_path.is_empty() {
     common::copy_files(&non_docker_data_path, &temp_data_dir).await?;
   }
@@ -79,10 +123,23 @@ async fn main() -> anyhow::Result<()> {
   let database_args = args.database_args;
   if database_args.has_args() {
     let db_type = database_args.db_type.unwrap();
-    let container_name = database_args.db_container_name.unwrap();
     let db_dump_file_name = format!("dump_{}.sql", now.format("%Y%m%d_%H%M%S"));
     let db_dump_path = format!("{temp_data_dir}/{db_dump_file_name}");
-    common::dump_db_by_docker(&db_dump_path, &container_name, &db_type).await?;
+    if database_args.is_host_mode() {
+      common::dump_db_by_host(
+        &db_dump_path,
+        &database_args.db_host.unwrap(),
+        &database_args.db_port,
+        &database_args.db_user.unwrap(),
+        &database_args.db_password.unwrap(),
+        &database_args.db_name.unwrap(),
+        &db_type,
+      )
+      .await?;
+    } else {
+      let container_name = database_args.db_container_name.unwrap();
+      common::dump_db_by_docker(&db_dump_path, &container_name, &db_type).await?;
+    }
   }
 
   // compress and sign with sha256 source data to temp data directory
@@ -91,6 +148,9 @@ async fn main() -> anyhow::Result<()> {
     &args.exclude,
     &data_compress_file_name,
     &data_compress_sha256_file_name,
+    &args.compression,
+    &args.compression_level,
+    &age_recipient,
   )
   .await?;
 
@@ -111,8 +171,18 @@ async fn main() -> anyhow::Result<()> {
     ];
     let upload_success_arc_clone = upload_success_arc.clone();
     let upload_failed_arc_clone = upload_failed_arc.clone();
+    let proxy = proxy.clone();
     let handle = std::thread::spawn(move || {
-      match common::upload_by_rclone(&remote_name, &remote_path, &local_path, &bin_path, &args.rotate) {
+      match common::upload_by_rclone(
+        &remote_name,
+        &remote_path,
+        &local_path,
+        &bin_path,
+        &args.rotate,
+        &proxy,
+        &max_retries,
+        &retry_backoff_ms,
+      ) {
         Ok(_) => {
           let mut vec = upload_success_arc_clone.lock().unwrap();
           vec.push(remote_name);
@@ -163,11 +233,52 @@ async fn main() -> anyhow::Result<()> {
       &ntfy_args.ntfy_token,
       &ntfy_args.ntfy_topic.unwrap(),
       &message,
+      &proxy,
+      &max_retries,
+      &retry_backoff_ms,
     )
     .await?;
   }
 
-  let duration = format!("{:.2}", (std::time::Instant::now() - start_time).as_secs_f64());
-  info!("All backups completed in {} seconds", duration);
+  Ok(())
+}
+
+async fn run_restore(
+  args: cli::RestoreArgs,
+  proxy: Option<String>,
+  max_retries: u32,
+  retry_backoff_ms: u64,
+) -> anyhow::Result<()> {
+  args.check_valid()?;
+
+  let restore_target = if std::path::Path::new(&args.restore_target).is_absolute() {
+    args.restore_target.clone()
+  } else {
+    format!("{}/{}", env::current_dir()?.to_string_lossy(), args.restore_target)
+  };
+
+  let temp_dir = TempDir::new()?;
+  env::set_current_dir(temp_dir.path())?;
+  info!("Restore in temp file: {}", temp_dir.path().to_string_lossy());
+
+  let rclone_args = args.rclone_args;
+  let mut remote_names = rclone_args.rclone_remote_name.unwrap();
+  let remote_name = remote_names.remove(0);
+  let remote_path = rclone_args.rclone_remote_path.unwrap();
+  let bin_path = rclone_args.rclone_bin_path.unwrap();
+
+  common::restore_by_rclone(
+    &remote_name,
+    &remote_path,
+    &bin_path,
+    &args.at,
+    &restore_target,
+    &args.age_identity,
+    &proxy,
+    &max_retries,
+    &retry_backoff_ms,
+  )
+  .await?;
+
   Ok(())
 }
@@ -1,5 +1,6 @@
+use anyhow::bail;
 use std::collections::HashMap;
-use tracing::error;
+use tracing::warn;
 
 pub async fn notify_by_ntfy(
   base_url: &String,
@@ -8,21 +9,53 @@ pub async fn notify_by_ntfy(
   token: &Option<String>,
   topic: &String,
   message: &String,
+  proxy: &Option<String>,
+  max_retries: &u32,
+  retry_backoff_ms: &u64,
 ) -> anyhow::Result<()> {
-  let client = reqwest::Client::new();
+  let mut client_builder = reqwest::Client::builder();
+  if let Some(proxy) = proxy {
+    client_builder = client_builder.proxy(reqwest::Proxy::all(proxy)?);
+  }
+  let client = client_builder.build()?;
+
   let mut data = HashMap::new();
   let auth_username = username.as_deref().unwrap_or("");
   let auth_password = token.as_ref().or(password.as_ref());
   data.insert("topic", topic);
   data.insert("message", message);
-  let response = client
-    .post(base_url)
-    .basic_auth(auth_username, auth_password)
-    .json(&data)
-    .send()
-    .await?;
-  if !response.status().is_success() {
-    error!("failed to send notification, response: {}", response.text().await?);
+
+  let mut attempt = 0;
+  loop {
+    attempt += 1;
+    let result = client
+      .post(base_url)
+      .basic_auth(auth_username, auth_password)
+      .json(&data)
+      .send()
+      .await;
+    let is_retryable_status = |status: reqwest::StatusCode| status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+    match result {
+      Ok(response) if response.status().is_success() => return Ok(()),
+      Ok(response) if is_retryable_status(response.status()) && attempt < *max_retries => {
+        warn!(
+          "ntfy responded with [{}] (attempt {attempt}/{max_retries}), retrying...",
+          response.status()
+        );
+        tokio::time::sleep(crate::common::backoff_duration(*retry_backoff_ms, attempt)).await;
+      }
+      Ok(response) => {
+        let status = response.status();
+        bail!(
+          "failed to send notification, status: {status}, response: {}",
+          response.text().await?
+        );
+      }
+      Err(err) if attempt >= *max_retries => return Err(err.into()),
+      Err(err) => {
+        warn!("failed to send notification (attempt {attempt}/{max_retries}), error: {err}, retrying...");
+        tokio::time::sleep(crate::common::backoff_duration(*retry_backoff_ms, attempt)).await;
+      }
+    }
   }
-  Ok(())
 }